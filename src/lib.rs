@@ -4,9 +4,11 @@
 //! addresses. Your test code can then target the locally bound server and make normal DNS
 //! requests.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::net::IpAddr;
 use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use async_trait::async_trait;
 use hickory_proto::rr::LowerName;
@@ -15,11 +17,87 @@ use hickory_server::authority::MessageResponseBuilder;
 use hickory_server::proto::op::Header;
 use hickory_server::proto::op::ResponseCode;
 use hickory_server::proto::rr::rdata::{A, AAAA};
-use hickory_server::proto::rr::{RData, Record};
+use hickory_server::proto::rr::{RData, Record, RecordType};
 use hickory_server::server::{
     Request, RequestHandler, ResponseHandler, ResponseInfo, ServerFuture,
 };
-use tokio::net::UdpSocket;
+use tokio::net::{TcpListener, UdpSocket};
+
+/// How long a TCP client is given to complete a request before the connection is dropped.
+const TCP_REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A canned response to send back for a query, overriding anything in the record store.
+///
+/// This is useful for forcing a particular [`ResponseCode`] to test a resolver's error handling,
+/// independent of what records have been configured for the name.
+#[derive(Clone, Debug)]
+pub enum Response {
+    /// Respond with the given [`ResponseCode`] and no records.
+    Code(ResponseCode),
+}
+
+/// A sequence of [`Response`]s to work through for a single name.
+///
+/// Each query against the name advances to the next entry, holding on the last one once the
+/// sequence is exhausted.
+#[derive(Clone, Debug)]
+struct ResponseSequence {
+    responses: Vec<Response>,
+    index: Arc<Mutex<usize>>,
+}
+
+impl ResponseSequence {
+    fn new(responses: Vec<Response>) -> Self {
+        Self {
+            responses,
+            index: Arc::new(Mutex::new(0)),
+        }
+    }
+
+    fn next(&self) -> Response {
+        let mut index = self.index.lock().unwrap();
+        let response = self.responses[*index].clone();
+
+        if *index + 1 < self.responses.len() {
+            *index += 1;
+        }
+
+        response
+    }
+}
+
+/// Per-mapping configuration controlling the TTL and fault injection for a set of records.
+///
+/// When a query is answered via a CNAME chain, these are applied per hop actually traversed: the
+/// `delay`s of every hop are summed, and the highest `drop_probability` among them is used, so a
+/// slow or flaky record anywhere in the chain affects the final response.
+#[derive(Clone, Copy, Debug)]
+pub struct RecordOptions {
+    /// The TTL to report for the records, in seconds.
+    pub ttl: u32,
+    /// An optional delay to wait before sending the response, simulating a slow nameserver.
+    pub delay: Option<Duration>,
+    /// The probability (between `0.0` and `1.0`) of silently dropping the response instead of
+    /// sending it, simulating a flaky nameserver for retry testing.
+    pub drop_probability: f64,
+}
+
+impl Default for RecordOptions {
+    fn default() -> Self {
+        Self {
+            ttl: 60,
+            delay: None,
+            drop_probability: 0.0,
+        }
+    }
+}
+
+/// A set of records along with the [`RecordOptions`] to serve them with.
+#[derive(Clone, Debug)]
+struct RecordSet {
+    data: Vec<RData>,
+    options: RecordOptions,
+}
 
 /// A simple mock server for DNS requests.
 ///
@@ -28,12 +106,16 @@ use tokio::net::UdpSocket;
 /// in a background task before making requests on the main thread.
 #[derive(Clone, Debug, Default)]
 pub struct Server {
-    store: HashMap<LowerName, Vec<IpAddr>>,
+    store: HashMap<LowerName, HashMap<RecordType, RecordSet>>,
+    responses: HashMap<LowerName, ResponseSequence>,
 }
 
 impl Server {
     /// Adds a mapping from a DNS record to some IP addresses.
     ///
+    /// This is a convenience wrapper around [`Server::add_rdata()`] for the common case of `A`
+    /// and `AAAA` records, splitting the given addresses into the appropriate record types.
+    ///
     /// # Example
     ///
     /// ```
@@ -45,9 +127,126 @@ impl Server {
     /// server.add_records("example.com", records).expect("Invalid hostname");
     /// ```
     pub fn add_records(&mut self, name: &str, records: Vec<IpAddr>) -> Result<(), ProtoError> {
+        self.add_records_with_options(name, records, RecordOptions::default())
+    }
+
+    /// Adds a mapping from a DNS record to some IP addresses, with custom [`RecordOptions`].
+    ///
+    /// This allows configuring a custom TTL (to test cache-expiry behaviour), an artificial delay
+    /// (to test timeout handling), or a chance of dropping the response entirely (to test retry
+    /// behaviour).
+    pub fn add_records_with_options(
+        &mut self,
+        name: &str,
+        records: Vec<IpAddr>,
+        options: RecordOptions,
+    ) -> Result<(), ProtoError> {
+        let mut a_records = Vec::new();
+        let mut aaaa_records = Vec::new();
+
+        for record in records {
+            match record {
+                IpAddr::V4(ipv4) => a_records.push(RData::A(A::from(ipv4))),
+                IpAddr::V6(ipv6) => aaaa_records.push(RData::AAAA(AAAA::from(ipv6))),
+            }
+        }
+
+        if !a_records.is_empty() {
+            self.add_rdata_with_options(name, RecordType::A, a_records, options)?;
+        }
+
+        if !aaaa_records.is_empty() {
+            self.add_rdata_with_options(name, RecordType::AAAA, aaaa_records, options)?;
+        }
+
+        Ok(())
+    }
+
+    /// Adds a mapping from a DNS record to some arbitrary [`RData`] of the given [`RecordType`].
+    ///
+    /// This allows mocking record types beyond `A`/`AAAA`, such as `TXT`, `MX`, `SRV`, `NS` and
+    /// `CNAME`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use dns_mock_server::Server;
+    /// # use hickory_server::proto::rr::rdata::TXT;
+    /// # use hickory_server::proto::rr::{RData, RecordType};
+    /// let mut server = Server::default();
+    /// let records = vec![RData::TXT(TXT::new(vec!["hello".to_string()]))];
+    ///
+    /// server
+    ///     .add_rdata("example.com", RecordType::TXT, records)
+    ///     .expect("Invalid hostname");
+    /// ```
+    pub fn add_rdata(
+        &mut self,
+        name: &str,
+        record_type: RecordType,
+        data: Vec<RData>,
+    ) -> Result<(), ProtoError> {
+        self.add_rdata_with_options(name, record_type, data, RecordOptions::default())
+    }
+
+    /// Adds a mapping from a DNS record to some arbitrary [`RData`], with custom
+    /// [`RecordOptions`].
+    pub fn add_rdata_with_options(
+        &mut self,
+        name: &str,
+        record_type: RecordType,
+        data: Vec<RData>,
+        options: RecordOptions,
+    ) -> Result<(), ProtoError> {
         let name = LowerName::from_str(name)?;
 
-        self.store.insert(name, records);
+        self.store
+            .entry(name)
+            .or_default()
+            .insert(record_type, RecordSet { data, options });
+
+        Ok(())
+    }
+
+    /// Pins a single [`Response`] for a name, overriding any records configured for it.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use dns_mock_server::{Response, Server};
+    /// # use hickory_server::proto::op::ResponseCode;
+    /// let mut server = Server::default();
+    ///
+    /// server
+    ///     .add_response("example.com", Response::Code(ResponseCode::NXDomain))
+    ///     .expect("Invalid hostname");
+    /// ```
+    pub fn add_response(&mut self, name: &str, response: Response) -> Result<(), ProtoError> {
+        self.add_response_sequence(name, vec![response])
+    }
+
+    /// Pins a sequence of [`Response`]s for a name, returning the next one on each successive
+    /// query and holding on the last entry once the sequence is exhausted.
+    ///
+    /// This is useful for testing retry and failover behaviour, e.g. answering with a
+    /// [`ResponseCode::ServFail`] before eventually succeeding.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `name` is not a valid hostname, or if `responses` is empty.
+    pub fn add_response_sequence(
+        &mut self,
+        name: &str,
+        responses: Vec<Response>,
+    ) -> Result<(), ProtoError> {
+        if responses.is_empty() {
+            return Err(ProtoError::from("responses must not be empty"));
+        }
+
+        let name = LowerName::from_str(name)?;
+
+        self.responses
+            .insert(name, ResponseSequence::new(responses));
 
         Ok(())
     }
@@ -63,6 +262,25 @@ impl Server {
 
         Ok(())
     }
+
+    /// Starts the mock server on the given [`UdpSocket`] and [`TcpListener`].
+    ///
+    /// This is useful for resolvers that fall back to TCP for truncated or oversized responses,
+    /// or that are explicitly configured to use it. Like [`Server::start()`], this should be run
+    /// in a background task using a method such as [`tokio::spawn`].
+    pub async fn start_with_tcp(
+        self,
+        socket: UdpSocket,
+        tcp: TcpListener,
+    ) -> Result<(), ProtoError> {
+        let mut server = ServerFuture::new(self);
+
+        server.register_socket(socket);
+        server.register_listener(tcp, TCP_REQUEST_TIMEOUT);
+        server.block_until_done().await?;
+
+        Ok(())
+    }
 }
 
 #[async_trait]
@@ -77,25 +295,87 @@ impl RequestHandler for Server {
         let mut header = Header::response_from_request(request.header());
         header.set_authoritative(true);
 
-        let name = request.queries()[0].name();
+        let Some(query) = request.queries().first() else {
+            header.set_response_code(ResponseCode::FormErr);
 
-        if let Some(entries) = self.store.get(name) {
-            let records: Vec<_> = entries
-                .iter()
-                .map(|entry| match entry {
-                    IpAddr::V4(ipv4) => RData::A(A::from(*ipv4)),
-                    IpAddr::V6(ipv6) => RData::AAAA(AAAA::from(*ipv6)),
-                })
-                .map(|rdata| Record::from_rdata(name.into(), 60, rdata))
-                .collect();
+            let response = builder.build_no_records(header);
+            return response_handler.send_response(response).await.unwrap();
+        };
 
-            let response = builder.build(header, records.iter(), &[], &[], &[]);
-            response_handler.send_response(response).await.unwrap()
-        } else {
+        let record_type = query.query_type();
+
+        if let Some(sequence) = self.responses.get(query.name()) {
+            match sequence.next() {
+                Response::Code(code) => header.set_response_code(code),
+            }
+
+            let response = builder.build_no_records(header);
+            return response_handler.send_response(response).await.unwrap();
+        }
+
+        let mut current: LowerName = query.name().clone();
+        let mut visited = HashSet::new();
+        let mut records = Vec::new();
+        let mut found_name = false;
+        let mut total_delay = Duration::ZERO;
+        let mut max_drop_probability: f64 = 0.0;
+
+        while visited.insert(current.clone()) {
+            let Some(by_type) = self.store.get(&current) else {
+                break;
+            };
+
+            found_name = true;
+
+            if record_type != RecordType::CNAME {
+                if let Some(cname_set) = by_type.get(&RecordType::CNAME) {
+                    records.extend(cname_set.data.iter().cloned().map(|rdata| {
+                        Record::from_rdata((&current).into(), cname_set.options.ttl, rdata)
+                    }));
+                    total_delay += cname_set.options.delay.unwrap_or_default();
+                    max_drop_probability =
+                        max_drop_probability.max(cname_set.options.drop_probability);
+
+                    if let Some(RData::CNAME(target)) = cname_set.data.first() {
+                        current = LowerName::from(target.0.clone());
+                        continue;
+                    }
+                }
+            }
+
+            if let Some(answer_set) = by_type.get(&record_type) {
+                records.extend(answer_set.data.iter().cloned().map(|rdata| {
+                    Record::from_rdata((&current).into(), answer_set.options.ttl, rdata)
+                }));
+                total_delay += answer_set.options.delay.unwrap_or_default();
+                max_drop_probability =
+                    max_drop_probability.max(answer_set.options.drop_probability);
+            }
+
+            break;
+        }
+
+        // Simulate slow or flaky nameservers, accumulating the delay and worst-case drop
+        // probability across every hop actually traversed in a CNAME chain.
+        if total_delay > Duration::ZERO {
+            tokio::time::sleep(total_delay).await;
+        }
+
+        if max_drop_probability > 0.0 && rand::random::<f64>() < max_drop_probability {
+            return header.into();
+        }
+
+        if !found_name {
+            // The name itself isn't in the store at all.
             header.set_response_code(ResponseCode::ServFail);
 
             let response = builder.build_no_records(header);
             response_handler.send_response(response).await.unwrap()
+        } else {
+            // The name exists but `records` may be empty if it has none of the requested type,
+            // i.e. a NODATA response rather than ServFail.
+            let response = builder.build(header, records.iter(), &[], &[], &[]);
+            response_handler.send_response(response).await.unwrap()
         }
     }
 }
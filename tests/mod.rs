@@ -1,5 +1,10 @@
 use std::net::{IpAddr, Ipv4Addr, SocketAddrV4};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
 
+use hickory_proto::op::{Message, MessageType, OpCode};
+use hickory_proto::rr::rdata::{CNAME, TXT};
+use hickory_proto::rr::{Name, RData, RecordType};
 use hickory_proto::xfer::Protocol;
 use hickory_resolver::config::{NameServerConfig, ResolverConfig};
 use hickory_resolver::name_server::TokioConnectionProvider;
@@ -7,9 +12,9 @@ use hickory_resolver::proto::op::ResponseCode;
 use hickory_resolver::ResolveErrorKind;
 use hickory_resolver::Resolver;
 use hickory_server::proto::ProtoErrorKind;
-use tokio::net::UdpSocket;
+use tokio::net::{TcpListener, UdpSocket};
 
-use dns_mock_server::{Response, Server};
+use dns_mock_server::{RecordOptions, Response, Server};
 
 type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
@@ -45,6 +50,235 @@ async fn can_query_dns_records_from_the_server() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn can_query_dns_records_over_tcp() -> Result<()> {
+    let expected_addr = IpAddr::V4(Ipv4Addr::new(1, 2, 3, 4));
+
+    let mut server = Server::default();
+    server.add_records("www.example.com.", vec![expected_addr])?;
+
+    let addr = SocketAddrV4::new(Ipv4Addr::LOCALHOST, 0);
+    let socket = UdpSocket::bind(&addr).await?;
+    let listener = TcpListener::bind(&addr).await?;
+
+    let local_addr = listener.local_addr()?;
+
+    tokio::spawn(async move {
+        server.start_with_tcp(socket, listener).await.unwrap();
+    });
+
+    let mut config = ResolverConfig::new();
+    let nameserver_config = NameServerConfig::new(local_addr, Protocol::Tcp);
+    config.add_name_server(nameserver_config);
+
+    let resolver =
+        Resolver::builder_with_config(config, TokioConnectionProvider::default()).build();
+    let result = resolver.lookup_ip("www.example.com.").await?;
+
+    let addrs: Vec<_> = result.into_iter().collect();
+
+    assert_eq!(addrs.len(), 1);
+    assert_eq!(addrs[0], expected_addr);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn can_query_txt_records_from_the_server() -> Result<()> {
+    let mut server = Server::default();
+    server.add_rdata(
+        "www.example.com.",
+        RecordType::TXT,
+        vec![RData::TXT(TXT::new(vec!["hello world".to_string()]))],
+    )?;
+
+    let addr = SocketAddrV4::new(Ipv4Addr::LOCALHOST, 0);
+    let socket = UdpSocket::bind(&addr).await?;
+
+    let local_addr = socket.local_addr()?;
+
+    tokio::spawn(async move {
+        server.start(socket).await.unwrap();
+    });
+
+    let mut config = ResolverConfig::new();
+    let nameserver_config = NameServerConfig::new(local_addr, Protocol::Udp);
+    config.add_name_server(nameserver_config);
+
+    let resolver =
+        Resolver::builder_with_config(config, TokioConnectionProvider::default()).build();
+    let result = resolver.txt_lookup("www.example.com.").await?;
+
+    let txts: Vec<_> = result.into_iter().collect();
+
+    assert_eq!(txts.len(), 1);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn can_resolve_cname_chains() -> Result<()> {
+    let expected_addr = IpAddr::V4(Ipv4Addr::new(1, 2, 3, 4));
+
+    let mut server = Server::default();
+    server.add_rdata(
+        "alias.example.com.",
+        RecordType::CNAME,
+        vec![RData::CNAME(CNAME(Name::from_str("www.example.com.")?))],
+    )?;
+    server.add_records("www.example.com.", vec![expected_addr])?;
+
+    let addr = SocketAddrV4::new(Ipv4Addr::LOCALHOST, 0);
+    let socket = UdpSocket::bind(&addr).await?;
+
+    let local_addr = socket.local_addr()?;
+
+    tokio::spawn(async move {
+        server.start(socket).await.unwrap();
+    });
+
+    let mut config = ResolverConfig::new();
+    let nameserver_config = NameServerConfig::new(local_addr, Protocol::Udp);
+    config.add_name_server(nameserver_config);
+
+    let resolver =
+        Resolver::builder_with_config(config, TokioConnectionProvider::default()).build();
+    let result = resolver.lookup_ip("alias.example.com.").await?;
+
+    let addrs: Vec<_> = result.into_iter().collect();
+
+    assert_eq!(addrs.len(), 1);
+    assert_eq!(addrs[0], expected_addr);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn can_configure_a_custom_ttl() -> Result<()> {
+    let expected_addr = IpAddr::V4(Ipv4Addr::new(1, 2, 3, 4));
+
+    let mut server = Server::default();
+    server.add_records_with_options(
+        "www.example.com.",
+        vec![expected_addr],
+        RecordOptions {
+            ttl: 1,
+            ..RecordOptions::default()
+        },
+    )?;
+
+    let addr = SocketAddrV4::new(Ipv4Addr::LOCALHOST, 0);
+    let socket = UdpSocket::bind(&addr).await?;
+
+    let local_addr = socket.local_addr()?;
+
+    tokio::spawn(async move {
+        server.start(socket).await.unwrap();
+    });
+
+    let mut config = ResolverConfig::new();
+    let nameserver_config = NameServerConfig::new(local_addr, Protocol::Udp);
+    config.add_name_server(nameserver_config);
+
+    let resolver =
+        Resolver::builder_with_config(config, TokioConnectionProvider::default()).build();
+    let result = resolver.lookup_ip("www.example.com.").await?;
+
+    let record = result
+        .as_lookup()
+        .record_iter()
+        .next()
+        .expect("no records returned");
+
+    assert_eq!(record.ttl(), 1);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn a_configured_delay_slows_down_the_response() -> Result<()> {
+    let expected_addr = IpAddr::V4(Ipv4Addr::new(1, 2, 3, 4));
+    let delay = Duration::from_millis(300);
+
+    let mut server = Server::default();
+    server.add_records_with_options(
+        "www.example.com.",
+        vec![expected_addr],
+        RecordOptions {
+            delay: Some(delay),
+            ..RecordOptions::default()
+        },
+    )?;
+
+    let addr = SocketAddrV4::new(Ipv4Addr::LOCALHOST, 0);
+    let socket = UdpSocket::bind(&addr).await?;
+
+    let local_addr = socket.local_addr()?;
+
+    tokio::spawn(async move {
+        server.start(socket).await.unwrap();
+    });
+
+    let mut config = ResolverConfig::new();
+    let nameserver_config = NameServerConfig::new(local_addr, Protocol::Udp);
+    config.add_name_server(nameserver_config);
+
+    let resolver =
+        Resolver::builder_with_config(config, TokioConnectionProvider::default()).build();
+
+    let started = Instant::now();
+    resolver.lookup_ip("www.example.com.").await?;
+    let elapsed = started.elapsed();
+
+    assert!(
+        elapsed >= delay,
+        "expected a response delayed by at least {delay:?}, got {elapsed:?}"
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn a_drop_probability_of_one_causes_the_client_to_see_no_response() -> Result<()> {
+    let expected_addr = IpAddr::V4(Ipv4Addr::new(1, 2, 3, 4));
+
+    let mut server = Server::default();
+    server.add_records_with_options(
+        "www.example.com.",
+        vec![expected_addr],
+        RecordOptions {
+            drop_probability: 1.0,
+            ..RecordOptions::default()
+        },
+    )?;
+
+    let addr = SocketAddrV4::new(Ipv4Addr::LOCALHOST, 0);
+    let socket = UdpSocket::bind(&addr).await?;
+
+    let local_addr = socket.local_addr()?;
+
+    tokio::spawn(async move {
+        server.start(socket).await.unwrap();
+    });
+
+    let mut config = ResolverConfig::new();
+    let nameserver_config = NameServerConfig::new(local_addr, Protocol::Udp);
+    config.add_name_server(nameserver_config);
+
+    let mut resolver_builder =
+        Resolver::builder_with_config(config, TokioConnectionProvider::default());
+    resolver_builder.options_mut().timeout = Duration::from_millis(200);
+    resolver_builder.options_mut().attempts = 0;
+
+    // Only query for A records, so the sole query is the one whose response gets dropped.
+    let resolver = resolver_builder.build();
+    let result = resolver.ipv4_lookup("www.example.com.").await;
+
+    assert!(result.is_err(), "expected a dropped response to time out");
+
+    Ok(())
+}
+
 #[tokio::test]
 async fn unknown_names_return_errors() -> Result<()> {
     let server = Server::default();
@@ -100,7 +334,8 @@ async fn can_query_desired_response_from_the_server() -> Result<()> {
     let nameserver_config = NameServerConfig::new(local_addr, Protocol::Udp);
     config.add_name_server(nameserver_config);
 
-    let resolver = AsyncResolver::tokio(config, ResolverOpts::default());
+    let resolver =
+        Resolver::builder_with_config(config, TokioConnectionProvider::default()).build();
     let result = resolver.lookup_ip("www.example.com.").await;
 
     match result.unwrap_err().kind() {
@@ -112,3 +347,144 @@ async fn can_query_desired_response_from_the_server() -> Result<()> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn querying_the_wrong_record_type_returns_nodata() -> Result<()> {
+    let expected_addr = IpAddr::V4(Ipv4Addr::new(1, 2, 3, 4));
+
+    let mut server = Server::default();
+    server.add_records("www.example.com.", vec![expected_addr])?;
+
+    let addr = SocketAddrV4::new(Ipv4Addr::LOCALHOST, 0);
+    let socket = UdpSocket::bind(&addr).await?;
+
+    let local_addr = socket.local_addr()?;
+
+    tokio::spawn(async move {
+        server.start(socket).await.unwrap();
+    });
+
+    let mut config = ResolverConfig::new();
+    let nameserver_config = NameServerConfig::new(local_addr, Protocol::Udp);
+    config.add_name_server(nameserver_config);
+
+    let resolver =
+        Resolver::builder_with_config(config, TokioConnectionProvider::default()).build();
+    let result = resolver.ipv6_lookup("www.example.com.").await;
+
+    let ResolveErrorKind::Proto(proto_error) = result.unwrap_err().kind() else {
+        return Err("got unexpected error kind back".into());
+    };
+
+    let ProtoErrorKind::NoRecordsFound { response_code, .. } = proto_error.kind() else {
+        return Err("got unexpected proto error kind back".into());
+    };
+
+    assert_eq!(*response_code, ResponseCode::NoError);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn a_request_with_no_queries_returns_form_err() -> Result<()> {
+    let server = Server::default();
+
+    let addr = SocketAddrV4::new(Ipv4Addr::LOCALHOST, 0);
+    let socket = UdpSocket::bind(&addr).await?;
+
+    let local_addr = socket.local_addr()?;
+
+    tokio::spawn(async move {
+        server.start(socket).await.unwrap();
+    });
+
+    // The resolver crate refuses to build a query-less request, so construct one by hand and
+    // talk to the server directly to exercise the empty-`queries()` guard.
+    let mut message = Message::new();
+    message.set_id(1);
+    message.set_message_type(MessageType::Query);
+    message.set_op_code(OpCode::Query);
+    message.set_recursion_desired(true);
+
+    let request = message.to_vec()?;
+
+    let client = UdpSocket::bind(&addr).await?;
+    client.connect(local_addr).await?;
+    client.send(&request).await?;
+
+    let mut buf = [0; 512];
+    let len = client.recv(&mut buf).await?;
+
+    let response = Message::from_vec(&buf[..len])?;
+
+    assert_eq!(response.response_code(), ResponseCode::FormErr);
+
+    Ok(())
+}
+
+#[test]
+fn add_response_sequence_rejects_an_empty_sequence() {
+    let mut server = Server::default();
+    let result = server.add_response_sequence("www.example.com.", vec![]);
+
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn can_query_a_scripted_response_sequence() -> Result<()> {
+    let mut server = Server::default();
+    server.add_response_sequence(
+        "www.example.com.",
+        vec![
+            Response::Code(ResponseCode::ServFail),
+            Response::Code(ResponseCode::NXDomain),
+        ],
+    )?;
+
+    let addr = SocketAddrV4::new(Ipv4Addr::LOCALHOST, 0);
+    let socket = UdpSocket::bind(&addr).await?;
+
+    let local_addr = socket.local_addr()?;
+
+    tokio::spawn(async move {
+        server.start(socket).await.unwrap();
+    });
+
+    let mut config = ResolverConfig::new();
+    let nameserver_config = NameServerConfig::new(local_addr, Protocol::Udp);
+    config.add_name_server(nameserver_config);
+
+    let resolver =
+        Resolver::builder_with_config(config, TokioConnectionProvider::default()).build();
+
+    // Each lookup only queries for A records, so it consumes exactly one entry of the sequence.
+    let first = resolver.ipv4_lookup("www.example.com.").await;
+    let ResolveErrorKind::Proto(proto_error) = first.unwrap_err().kind() else {
+        return Err("got unexpected error kind back".into());
+    };
+    let ProtoErrorKind::NoRecordsFound { response_code, .. } = proto_error.kind() else {
+        return Err("got unexpected proto error kind back".into());
+    };
+    assert_eq!(*response_code, ResponseCode::ServFail);
+
+    let second = resolver.ipv4_lookup("www.example.com.").await;
+    let ResolveErrorKind::Proto(proto_error) = second.unwrap_err().kind() else {
+        return Err("got unexpected error kind back".into());
+    };
+    let ProtoErrorKind::NoRecordsFound { response_code, .. } = proto_error.kind() else {
+        return Err("got unexpected proto error kind back".into());
+    };
+    assert_eq!(*response_code, ResponseCode::NXDomain);
+
+    // The sequence is exhausted, so it should keep returning the last entry.
+    let third = resolver.ipv4_lookup("www.example.com.").await;
+    let ResolveErrorKind::Proto(proto_error) = third.unwrap_err().kind() else {
+        return Err("got unexpected error kind back".into());
+    };
+    let ProtoErrorKind::NoRecordsFound { response_code, .. } = proto_error.kind() else {
+        return Err("got unexpected proto error kind back".into());
+    };
+    assert_eq!(*response_code, ResponseCode::NXDomain);
+
+    Ok(())
+}